@@ -1,7 +1,17 @@
 //! Delays
 
+use core::cell::RefCell;
 use core::convert::Infallible;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use critical_section::Mutex;
+use eh1::delay::DelayNs;
 use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::timer::{CountDown, Periodic};
+use embedded_hal_async::delay::DelayUs as AsyncDelayUs;
+use fugit::{MicrosDurationU32, MillisDurationU32, NanosDurationU32};
 
 /// Use RISCV machine-mode cycle counter (`mcycle`) as a delay provider.
 ///
@@ -41,6 +51,57 @@ impl McycleDelay {
 
         while McycleDelay::cycles_since(start_cycle_count) <= cycle_count {}
     }
+
+    /// Delays for `count` units of `1/divisor` seconds, splitting the wait into
+    /// chunks small enough that `chunk * core_frequency` cannot overflow `u64`.
+    ///
+    /// This is the same "delay in chunks" trick cortex-m's SysTick delay uses,
+    /// applied here so a single long, high core clock delay can't wrap around
+    /// to a far shorter (or zero-length) wait.
+    #[inline]
+    fn delay_scaled(&self, count: u64, divisor: u64) {
+        let core_frequency = self.core_frequency as u64;
+        if core_frequency == 0 {
+            // Nothing to convert cycles to/from - matches the pre-chunking
+            // behaviour of a zero-frequency `McycleDelay` no-op'ing instead
+            // of dividing by zero
+            return;
+        }
+        let max_count = u64::MAX / core_frequency;
+
+        let mut remaining = count;
+        while remaining > max_count {
+            McycleDelay::delay_cycles((max_count * core_frequency) / divisor);
+            remaining -= max_count;
+        }
+        McycleDelay::delay_cycles((remaining * core_frequency) / divisor);
+    }
+
+    /// Performs a busy-wait loop until the number of nanoseconds `ns` has elapsed.
+    ///
+    /// The minimum resolvable delay is one core clock cycle; the ns-to-cycle
+    /// conversion is rounded up so the wait is never shorter than requested.
+    /// As with [`delay_scaled`](Self::delay_scaled), the wait is split into
+    /// chunks so a large `ns` at a high core frequency can't overflow `u64`.
+    #[inline]
+    pub fn try_delay_ns(&mut self, ns: u64) -> Result<(), Infallible> {
+        let core_frequency = self.core_frequency as u64;
+        if core_frequency == 0 {
+            return Ok(());
+        }
+        // Leave headroom for the `+ 999_999_999` round-up below so it can't
+        // itself overflow once a chunk's cycle count is close to u64::MAX
+        let max_ns = (u64::MAX - 999_999_999) / core_frequency;
+
+        let mut remaining = ns;
+        while remaining > max_ns {
+            McycleDelay::delay_cycles((max_ns * core_frequency + 999_999_999) / 1_000_000_000);
+            remaining -= max_ns;
+        }
+        McycleDelay::delay_cycles((remaining * core_frequency + 999_999_999) / 1_000_000_000);
+
+        Ok(())
+    }
 }
 
 // McycleDelay is 64bit, so implement our delays in terms of 64bit math
@@ -51,7 +112,7 @@ impl DelayUs<u64> for McycleDelay {
     /// Performs a busy-wait loop until the number of microseconds `us` has elapsed
     #[inline]
     fn try_delay_us(&mut self, us: u64) -> Result<(), Infallible> {
-        McycleDelay::delay_cycles((us * (self.core_frequency as u64)) / 1_000_000);
+        self.delay_scaled(us, 1_000_000);
 
         Ok(())
     }
@@ -63,7 +124,7 @@ impl DelayMs<u64> for McycleDelay {
     /// Performs a busy-wait loop until the number of milliseconds `ms` has elapsed
     #[inline]
     fn try_delay_ms(&mut self, ms: u64) -> Result<(), Infallible> {
-        McycleDelay::delay_cycles((ms * (self.core_frequency as u64)) / 1000);
+        self.delay_scaled(ms, 1000);
 
         Ok(())
     }
@@ -210,3 +271,257 @@ impl DelayMs<u8> for McycleDelay {
         self.try_delay_ms(ms as u64)
     }
 }
+
+/// Use RISCV machine-mode cycle counter (`mcycle`) as a non-blocking count-down timer.
+///
+/// Unlike [`McycleDelay`] this does not block the CPU while the count elapses -
+/// call `try_wait` repeatedly (e.g. from a polling loop) to find out when the
+/// configured count, in microseconds, has elapsed.
+#[derive(Copy, Clone)]
+pub struct McycleCountDown {
+    core_frequency: u32,
+    start: u64,
+    target: u64,
+}
+
+impl McycleCountDown {
+    /// Constructs the count-down provider based on core clock frequency `freq`
+    pub fn new(freq: u32) -> Self {
+        Self {
+            core_frequency: freq,
+            start: 0,
+            target: 0,
+        }
+    }
+}
+
+impl CountDown for McycleCountDown {
+    type Error = Infallible;
+    type Time = u64;
+
+    /// Starts a new count-down of `count` microseconds
+    fn try_start<T>(&mut self, count: T) -> Result<(), Infallible>
+    where
+        T: Into<Self::Time>,
+    {
+        self.start = McycleDelay::get_cycle_count();
+        // Widen to u128 for the multiply so a large `count` at a high core
+        // frequency can't silently wrap, then saturate back down to u64
+        let target = (count.into() as u128) * (self.core_frequency as u128) / 1_000_000;
+        self.target = target.min(u64::MAX as u128) as u64;
+
+        Ok(())
+    }
+
+    /// Returns `Ok(())` once the configured count has elapsed, `Err(nb::Error::WouldBlock)` otherwise
+    fn try_wait(&mut self) -> nb::Result<(), Infallible> {
+        if McycleDelay::cycles_since(self.start) >= self.target {
+            // Re-arm for the next period instead of resetting to `now`, so a
+            // Periodic caller doesn't drift by the time spent between periods
+            self.start += self.target;
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+// McycleCountDown never stops counting down on its own - every elapsed
+// period is automatically followed by the next one
+impl Periodic for McycleCountDown {}
+
+/// Blocking delay for a [`fugit`] duration, mirroring [`DelayUs`]/[`DelayMs`]
+/// but carrying its time unit in the type instead of the method name
+pub trait DelayFugit<T> {
+    /// Performs a busy-wait loop until the duration `d` has elapsed
+    fn delay(&mut self, d: T);
+}
+
+impl DelayFugit<MicrosDurationU32> for McycleDelay {
+    #[inline]
+    fn delay(&mut self, d: MicrosDurationU32) {
+        self.try_delay_us(d.ticks() as u64).unwrap();
+    }
+}
+
+impl DelayFugit<MillisDurationU32> for McycleDelay {
+    #[inline]
+    fn delay(&mut self, d: MillisDurationU32) {
+        self.try_delay_ms(d.ticks() as u64).unwrap();
+    }
+}
+
+impl DelayFugit<NanosDurationU32> for McycleDelay {
+    #[inline]
+    fn delay(&mut self, d: NanosDurationU32) {
+        self.try_delay_ns(d.ticks() as u64).unwrap();
+    }
+}
+
+// Implementation of the embedded-hal 1.0 `DelayNs` trait, which supersedes the
+// separate `DelayUs`/`DelayMs` traits above. The `embedded-hal 0.2`-style
+// `try_*` methods are kept alongside it for users still on the older trait set.
+impl DelayNs for McycleDelay {
+    #[inline]
+    fn delay_ns(&mut self, ns: u32) {
+        self.try_delay_ns(ns as u64).unwrap();
+    }
+
+    #[inline]
+    fn delay_us(&mut self, us: u32) {
+        self.try_delay_us(us as u64).unwrap();
+    }
+
+    #[inline]
+    fn delay_ms(&mut self, ms: u32) {
+        self.try_delay_ms(ms as u64).unwrap();
+    }
+}
+
+// BL602's CLINT is memory-mapped at a fixed address and isn't yet exposed
+// through bl602-pac, so the machine timer registers are accessed directly
+const CLINT_MTIME: *const u64 = 0x0200_bff8 as *const u64;
+const CLINT_MTIMECMP: *mut u64 = 0x0200_4000 as *mut u64;
+
+/// Tick rate of the CLINT machine timer (`mtime`), in Hz. BL602 clocks it from
+/// a fixed 32 kHz oscillator, independent of the configurable core clock that
+/// [`McycleDelay`] is driven from.
+const MTIME_FREQ_HZ: u64 = 32_000;
+
+static MTIMER_WAKER: Mutex<RefCell<Option<Waker>>> = Mutex::new(RefCell::new(None));
+// `mtime`/`mtimecmp` is a single hardware comparator, so only one `MtimerWait`
+// can be outstanding at a time. This records which one currently owns it, so
+// a second concurrent wait can be told apart from the same future re-polling.
+static MTIMER_ARMED_TARGET: Mutex<RefCell<Option<u64>>> = Mutex::new(RefCell::new(None));
+
+#[inline]
+fn read_mtime() -> u64 {
+    unsafe { CLINT_MTIME.read_volatile() }
+}
+
+#[inline]
+fn write_mtimecmp(value: u64) {
+    unsafe { CLINT_MTIMECMP.write_volatile(value) }
+}
+
+/// Future returned by [`MtimerDelay`] that completes once `mtime` reaches `target`
+struct MtimerWait {
+    target: u64,
+}
+
+impl Future for MtimerWait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if read_mtime() >= self.target {
+            return Poll::Ready(());
+        }
+
+        critical_section::with(|cs| {
+            let armed = *MTIMER_ARMED_TARGET.borrow_ref(cs);
+            if let Some(armed_target) = armed {
+                if armed_target != self.target {
+                    // `mtimecmp` is already owned by a different outstanding
+                    // wait - arming it for `self.target` would silently drop
+                    // that wait's Waker and hang it forever. Fail loudly
+                    // instead: only one MtimerDelay wait may be outstanding
+                    // at a time.
+                    panic!("MtimerDelay only supports one outstanding wait at a time");
+                }
+            }
+            *MTIMER_ARMED_TARGET.borrow_ref_mut(cs) = Some(self.target);
+            *MTIMER_WAKER.borrow_ref_mut(cs) = Some(cx.waker().clone());
+        });
+        write_mtimecmp(self.target);
+        unsafe {
+            riscv::register::mie::set_mtimer();
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for MtimerWait {
+    fn drop(&mut self) {
+        // If this wait is cancelled (e.g. a `select!` picked another branch)
+        // before `mtime` reaches `target`, disarm `mtimer` and release our
+        // claim on it - otherwise the next, entirely sequential `MtimerDelay`
+        // wait would find a stale `MTIMER_ARMED_TARGET` and panic as though a
+        // second wait were genuinely outstanding.
+        critical_section::with(|cs| {
+            if *MTIMER_ARMED_TARGET.borrow_ref(cs) == Some(self.target) {
+                unsafe {
+                    riscv::register::mie::clear_mtimer();
+                }
+                write_mtimecmp(u64::MAX);
+                *MTIMER_ARMED_TARGET.borrow_ref_mut(cs) = None;
+                MTIMER_WAKER.borrow_ref_mut(cs).take();
+            }
+        });
+    }
+}
+
+/// Async delay provider built on BL602's RISC-V machine timer (`mtime`/`mtimecmp`
+/// in the CLINT), for use where [`McycleDelay`]'s busy-wait would block an executor
+/// from running other tasks.
+///
+/// `mtime`/`mtimecmp` is a single hardware comparator shared by every
+/// `MtimerDelay`, so only one delay may be outstanding at a time - e.g. don't
+/// `select!` over two `MtimerDelay` futures, or await one from two tasks at
+/// once. Starting a second wait before the first completes panics rather than
+/// silently hanging the first task.
+#[derive(Copy, Clone, Default)]
+pub struct MtimerDelay;
+
+impl MtimerDelay {
+    /// Constructs the async delay provider
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Busy-waits until the given number of `mtime` ticks from now has elapsed.
+    /// Provided as a fallback for use without an executor.
+    pub fn block_for(&mut self, ticks: u64) {
+        let target = read_mtime() + ticks;
+        while read_mtime() < target {}
+    }
+}
+
+impl AsyncDelayUs for MtimerDelay {
+    type Error = Infallible;
+
+    /// Yields until the number of microseconds `us` has elapsed, to the
+    /// resolution of one `mtime` tick (the tick count is rounded up)
+    async fn delay_us(&mut self, us: u32) -> Result<(), Infallible> {
+        let target = read_mtime() + (us as u64 * MTIME_FREQ_HZ + 999_999) / 1_000_000;
+        MtimerWait { target }.await;
+
+        Ok(())
+    }
+
+    /// Yields until the number of milliseconds `ms` has elapsed, to the
+    /// resolution of one `mtime` tick (the tick count is rounded up)
+    async fn delay_ms(&mut self, ms: u32) -> Result<(), Infallible> {
+        let target = read_mtime() + (ms as u64 * MTIME_FREQ_HZ + 999) / 1000;
+        MtimerWait { target }.await;
+
+        Ok(())
+    }
+}
+
+/// Machine-timer interrupt handler: disables further firing of `mtimer` and
+/// wakes the task waiting on a [`MtimerDelay`] future
+#[no_mangle]
+pub extern "C" fn MachineTimer() {
+    unsafe {
+        riscv::register::mie::clear_mtimer();
+    }
+    write_mtimecmp(u64::MAX);
+
+    critical_section::with(|cs| {
+        *MTIMER_ARMED_TARGET.borrow_ref_mut(cs) = None;
+        if let Some(waker) = MTIMER_WAKER.borrow_ref_mut(cs).take() {
+            waker.wake();
+        }
+    });
+}